@@ -0,0 +1,89 @@
+//! futex（fast userspace mutex），用于支撑 pthread 的 mutex/条件变量等同步原语
+//!
+//! 等待队列以用户字所在的**物理地址**为键，这样同一个共享内存页即使被映射到不同
+//! 进程的不同虚拟地址，也能正确地互相唤醒
+
+use alloc::{collections::BTreeMap, collections::VecDeque, sync::Arc};
+
+use defines::error::{errno, Result};
+use memory::VirtAddr;
+use spin::Mutex;
+use user_check::UserCheck;
+
+use crate::{
+    hart::{local_hart, wake_hart},
+    thread::{Thread, ThreadStatus},
+};
+
+/// `key` 为用户字的物理地址，`value` 为在它上面等待的线程，按入队顺序排列
+static FUTEX_TABLE: Mutex<BTreeMap<usize, VecDeque<Arc<Thread>>>> = Mutex::new(BTreeMap::new());
+
+pub const FUTEX_WAIT: u32 = 0;
+pub const FUTEX_WAKE: u32 = 1;
+
+/// 将当前进程地址空间中的 `uaddr` 转换为物理地址，作为 futex 表的键
+fn user_word_paddr(uaddr: *const u32) -> Result<usize> {
+    let process = unsafe { (*local_hart()).curr_process() };
+    process.lock_inner_with(|inner| {
+        inner
+            .memory_set
+            .translate_va(VirtAddr(uaddr as usize))
+            .map(|pa| pa.0)
+            .ok_or(errno::EFAULT)
+    })
+}
+
+/// `FUTEX_WAIT`：如果 `*uaddr == expected`，则把当前线程挂到 `uaddr` 对应的等待
+/// 队列上并让出 hart，直到被 `FUTEX_WAKE` 唤醒
+///
+/// 目前不支持超时：内核的定时器子系统尚未就绪，`sys_futex` 会在进入这里之前就
+/// 拒绝带 `timeout` 的调用，调用到这里的等待一定是无限期的
+pub async fn futex_wait(uaddr: *const u32, expected: u32) -> Result {
+    let key = user_word_paddr(uaddr)?;
+    let thread = unsafe { (*local_hart()).curr_thread() };
+
+    {
+        // 必须在持有 `FUTEX_TABLE` 锁的情况下比较用户字的值并入队，否则在读到
+        // 值之后、真正挂到等待队列之前，`FUTEX_WAKE` 可能已经发生并被错过
+        let mut table = FUTEX_TABLE.lock();
+        let current = UserCheck::new(uaddr).check_ptr().map_err(|_| errno::EFAULT)?;
+        if *current != expected {
+            return Err(errno::EAGAIN);
+        }
+        thread.lock_inner(|inner| inner.thread_status = ThreadStatus::Blocked);
+        table.entry(key).or_default().push_back(Arc::clone(&thread));
+    }
+
+    while thread.lock_inner(|inner| inner.thread_status) == ThreadStatus::Blocked {
+        executor::yield_now().await;
+    }
+    Ok(0)
+}
+
+/// `FUTEX_WAKE`：唤醒最多 `n` 个在 `uaddr` 上等待的线程，返回实际唤醒的数量
+pub fn futex_wake(uaddr: *const u32, n: u32) -> Result {
+    let key = user_word_paddr(uaddr)?;
+    let mut table = FUTEX_TABLE.lock();
+    let Some(waiters) = table.get_mut(&key) else {
+        return Ok(0);
+    };
+
+    let mut woken = 0usize;
+    while woken < n as usize {
+        let Some(waiter) = waiters.pop_front() else {
+            break;
+        };
+        let last_hart = waiter.lock_inner(|inner| {
+            inner.thread_status = ThreadStatus::Ready;
+            inner.last_hart
+        });
+        // 被唤醒的线程可能阻塞在别的 hart 上，那个 hart 的 executor 循环并不知道
+        // 有新任务就绪，需要用 IPI 把它叫醒，让它重新检查运行队列
+        wake_hart(last_hart);
+        woken += 1;
+    }
+    if waiters.is_empty() {
+        table.remove(&key);
+    }
+    Ok(woken as isize)
+}
@@ -6,7 +6,8 @@ use core::{
 use alloc::sync::Arc;
 use defines::{config::HART_NUM, trap_context::TrapContext};
 use memory::KERNEL_SPACE;
-use riscv::register::sstatus;
+use riscv::register::{sscratch, sstatus};
+use spin::Mutex;
 
 use crate::{process::Process, thread::Thread};
 
@@ -16,20 +17,21 @@ static mut HARTS: [Hart; HART_NUM] = [const { Hart::new() }; HART_NUM];
 
 /// 可以认为代表一个处理器。存放一些 per-hart 的数据
 ///
-/// 因此，一般可以假定不会被并行访问
+/// 现在已经支持多核启动（见 [`__hart_entry`]），线程可能在任意时刻被其他 hart
+/// 通过 [`Hart::replace_thread`] 换下去，因此 `thread` 字段需要加锁保护，不能再
+/// 假定不会被并行访问
 #[repr(align(32))]
 pub struct Hart {
     hart_id: usize,
-    //TODO: 内核线程是不是会不太一样？
-    /// 当前 hart 上正在运行的线程。
-    thread: Option<Arc<Thread>>,
+    /// 当前 hart 上正在运行的线程
+    thread: Mutex<Option<Arc<Thread>>>,
 }
 
 impl Hart {
     pub const fn new() -> Hart {
         Hart {
             hart_id: 0,
-            thread: None,
+            thread: Mutex::new(None),
         }
     }
 
@@ -40,17 +42,23 @@ impl Hart {
     #[track_caller]
     pub fn trap_context(&self) -> *mut TrapContext {
         self.thread
+            .lock()
             .as_ref()
             .expect("Only user task has trap context")
             .lock_inner(|inner| &mut inner.trap_context as _)
     }
 
-    pub fn replace_thread(&mut self, new_thread: Option<Arc<Thread>>) -> Option<Arc<Thread>> {
-        core::mem::replace(&mut self.thread, new_thread)
+    pub fn replace_thread(&self, new_thread: Option<Arc<Thread>>) -> Option<Arc<Thread>> {
+        if let Some(thread) = &new_thread {
+            // 记录下该线程被调度到了哪个 hart，供之后 `wake_hart` 使用，见
+            // `ThreadInner::last_hart` 的说明
+            thread.lock_inner(|inner| inner.last_hart = self.hart_id);
+        }
+        core::mem::replace(&mut *self.thread.lock(), new_thread)
     }
 
-    pub fn curr_thread(&self) -> &Thread {
-        self.thread.as_ref().unwrap()
+    pub fn curr_thread(&self) -> Arc<Thread> {
+        Arc::clone(self.thread.lock().as_ref().unwrap())
     }
 
     pub fn curr_process(&self) -> Arc<Process> {
@@ -87,13 +95,13 @@ pub extern "C" fn __hart_entry(hart_id: usize) -> ! {
         info!("Init hart {hart_id} started");
         INIT_FINISHED.store(true, Ordering::SeqCst);
 
-        // 将下面的代码取消注释即可启动多核
-        // for i in 0..HART_NUM {
-        //     if i == hart_id {
-        //         continue;
-        //     }
-        //     utils::arch::hart_start(i, utils::config::HART_START_ADDR);
-        // }
+        // 启动其余的 hart，让它们各自走到下面的 `else` 分支
+        for i in 0..HART_NUM {
+            if i == hart_id {
+                continue;
+            }
+            utils::arch::hart_start(i, utils::config::HART_START_ADDR);
+        }
     } else {
         while !INIT_FINISHED.load(Ordering::SeqCst) {
             core::hint::spin_loop()
@@ -114,6 +122,21 @@ pub extern "C" fn __hart_entry(hart_id: usize) -> ! {
 
 /// 设置当前 hart 的 `Hart` 结构，将 `tp` 设置为其地址
 ///
+/// RISC-V 的用户态约定 `tp` 为线程指针（TLS 基址），而内核一直用它存放
+/// [`Hart`] 指针，两者会互相冲突：多线程的用户程序一旦设置了自己的 `tp`，内核
+/// 再读 `tp` 就会读到用户数据而不是 `Hart` 指针。
+///
+/// 解决办法是让 `tp` 只在内核态下代表 `Hart` 指针，`sscratch` 则在用户态运行时
+/// 保存同一个值；在 trap 入口/出口各执行一次 [`swap_tp_sscratch`]（入口在
+/// `entry.S` 的 `__alltraps` 最前面，出口在 `__restore` 恢复完其余寄存器之后），
+/// 就可以在两者之间正确切换：
+/// - 从用户态陷入内核：交换后 `tp` 变回 `Hart` 指针，`sscratch` 则保存了用户的
+///   `tp`（需要由调用者存入线程的 [`ThreadInner::user_tp`]）
+/// - 从内核返回用户态：先把线程的 `user_tp` 写入 `sscratch`，再交换一次，`tp`
+///   就变成了用户的 TLS 基址，而 `Hart` 指针被换回 `sscratch` 留给下次陷入使用
+///
+/// [`ThreadInner::user_tp`]: crate::thread::ThreadInner::user_tp
+///
 /// # Safety
 ///
 /// 需保证由不同 hart 调用
@@ -122,6 +145,43 @@ unsafe fn set_local_hart(hart_id: usize) {
     hart.hart_id = hart_id;
     let hart_addr = hart as *const _ as usize;
     asm!("mv tp, {}", in(reg) hart_addr);
+    // 初始化时还没有用户线程运行，`sscratch` 暂时也指向 `Hart`，保证第一次
+    // `swap_tp_sscratch` 之前两者语义一致
+    sscratch::write(hart_addr);
+}
+
+/// 交换 `tp` 与 `sscratch`，用于在内核态/用户态之间切换 `tp` 的含义
+///
+/// 必须在 trap 入口保存其余寄存器之前、以及 trap 出口恢复其余寄存器之后各调用
+/// 一次，具体见 [`set_local_hart`] 的说明
+///
+/// # Safety
+///
+/// 只能在 trap 入口/出口处、且知道 `sscratch` 当前保存的是期望交换目标时调用
+#[inline(always)]
+pub unsafe fn swap_tp_sscratch() {
+    asm!("csrrw tp, sscratch, tp");
+}
+
+/// 陷入内核、`entry.S` 的 `__alltraps` 执行完 [`swap_tp_sscratch`] 之后应该
+/// 立刻调用一次：这时 `sscratch` 保存着刚刚从 `tp` 换出来的用户 `tp`，把它存回
+/// `thread` 里（用户可能用 `set_thread_area` 之类的调用换过 `tp`），然后把
+/// `sscratch` 恢复成 `Hart` 指针本身，这样内核代码执行期间任何时候读
+/// `sscratch` 拿到的都是 `Hart` 指针
+pub fn on_trap_enter(thread: &Thread) {
+    let hart_addr = local_hart() as usize;
+    let user_tp = sscratch::read();
+    thread.lock_inner(|inner| inner.user_tp = user_tp);
+    unsafe { sscratch::write(hart_addr) };
+}
+
+/// 即将返回用户态、在 `entry.S` 的 `__restore` 执行 [`swap_tp_sscratch`] 之前
+/// 应该调用一次：把 `thread` 的 `user_tp` 写入 `sscratch`，这样
+/// `swap_tp_sscratch` 换完之后 `tp` 就是正确的用户线程指针，而 `Hart` 指针被
+/// 换回 `sscratch` 留给下一次陷入使用
+pub fn on_trap_leave(thread: &Thread) {
+    let user_tp = thread.lock_inner(|inner| inner.user_tp);
+    unsafe { sscratch::write(user_tp) };
 }
 
 pub fn local_hart() -> *const Hart {
@@ -143,3 +203,16 @@ pub fn local_hart_mut() -> *mut Hart {
 pub fn curr_process() -> Arc<Process> {
     unsafe { (*local_hart()).curr_process() }
 }
+
+/// 通过 IPI 唤醒 `hart_id` 上可能正在等待新任务的 hart
+///
+/// 调度器把一个线程从阻塞状态变为就绪、放回运行队列之后，如果该线程是被另一个
+/// hart 唤醒的，那个 hart 自己的 executor 循环并不知道有新任务到来，需要用这个
+/// 函数发一个核间中断把它唤醒，让它重新检查运行队列
+pub fn wake_hart(hart_id: usize) {
+    if hart_id == unsafe { (*local_hart()).hart_id() } {
+        // 在自己这个 hart 上唤醒自己，不需要 IPI
+        return;
+    }
+    utils::arch::send_ipi(1usize << hart_id);
+}
@@ -0,0 +1,78 @@
+//! 进程控制块
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use defines::signal::KSignalSet;
+use memory::MemorySet;
+use spin::Mutex;
+
+use crate::{
+    signal::SignalHandlers,
+    thread::{Thread, ThreadStatus, UserStackAllocator},
+};
+
+/// 进程控制块
+pub struct Process {
+    pub pid: usize,
+    inner: Mutex<ProcessInner>,
+}
+
+/// [`Process`] 中需要被锁保护的部分
+pub struct ProcessInner {
+    pub memory_set: MemorySet,
+    /// 该进程安装的信号处理函数，同一进程下的所有线程共享
+    pub signal_handlers: SignalHandlers,
+    /// 尚未被任何线程领走的、发给整个进程的信号（例如 `kill` 发送的信号，相对
+    /// 地 `sys_tkill` 发送的信号记在 [`ThreadInner::pending_signals`] 里）
+    ///
+    /// [`ThreadInner::pending_signals`]: crate::thread::ThreadInner::pending_signals
+    pub pending_signals: KSignalSet,
+    /// 该进程下所有存活的线程
+    pub threads: Vec<Arc<Thread>>,
+    /// 该进程的用户栈分配器，所有线程共用，以便线程退出后空出的栈区域能被其他
+    /// 线程复用
+    pub stack_allocator: UserStackAllocator,
+    /// 进程退出码，只有在进程退出之后才有意义
+    pub exit_code: i32,
+}
+
+impl Process {
+    pub fn new(pid: usize, memory_set: MemorySet) -> Arc<Self> {
+        Arc::new(Self {
+            pid,
+            inner: Mutex::new(ProcessInner {
+                memory_set,
+                signal_handlers: SignalHandlers::default(),
+                pending_signals: KSignalSet::empty(),
+                threads: Vec::new(),
+                stack_allocator: UserStackAllocator::new(),
+                exit_code: 0,
+            }),
+        })
+    }
+
+    /// 锁 inner 然后进行操作。这应该是访问 inner 的唯一方式
+    pub fn lock_inner_with<T>(&self, f: impl FnOnce(&mut ProcessInner) -> T) -> T {
+        f(&mut self.inner.lock())
+    }
+}
+
+/// 终止整个进程：记录退出码，并让该进程下所有线程退出
+///
+/// 调用者一般只持有 [`Thread::process`] 这个弱引用，因此这里直接接受
+/// `Weak<Process>`；如果进程已经被释放（正常不应发生，因为调用者自己就活在这个
+/// 进程下）则什么都不做
+pub fn exit_process(process: &Weak<Process>, exit_code: i32) {
+    let Some(process) = process.upgrade() else {
+        return;
+    };
+    process.lock_inner_with(|inner| {
+        inner.exit_code = exit_code;
+        for thread in &inner.threads {
+            thread.lock_inner(|inner| inner.thread_status = ThreadStatus::Zombie);
+        }
+    });
+}
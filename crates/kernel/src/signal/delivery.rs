@@ -0,0 +1,142 @@
+//! 信号投递：在内核即将返回用户态之前，把一个 pending 的信号真正交给线程处理
+
+use defines::{
+    error::Result,
+    signal::{KSignalAction, KSignalSet, Signal, SignalActionFlags},
+};
+use user_check::UserCheckMut;
+
+use crate::{
+    process::{exit_process, Process},
+    thread::{Thread, ThreadStatus},
+};
+
+use super::{sig_trampoline_addr, SignalContext};
+
+/// 处理函数为 `SIG_DFL` 时的取值
+const SIG_DFL: usize = 0;
+/// 处理函数为 `SIG_IGN` 时的取值
+const SIG_IGN: usize = 1;
+
+/// 在从内核返回用户态之前调用，检查并处理当前线程待处理的信号
+///
+/// 会依次考虑线程私有的 pending 集合和进程共享的 pending 集合，挑出其中编号
+/// 最小、且没有被 `signal_mask` 屏蔽的信号进行处理。如果确实要进入用户 handler，
+/// 会修改 `trap_context`（`sepc`/`sp`/`a0`/`ra`），调用者之后按正常流程返回用户态
+/// 即可落入 handler
+pub fn deliver_pending_signal(process: &Process, thread: &Thread) {
+    let Some(signal) = pick_pending_signal(process, thread) else {
+        return;
+    };
+
+    let action = process.lock_inner_with(|inner| inner.signal_handlers.action(signal).clone());
+
+    match action.handler as usize {
+        SIG_IGN => {}
+        SIG_DFL => apply_default_action(thread, signal),
+        _ => {
+            if push_signal_frame(process, thread, signal, &action).is_err() {
+                // TODO:[blocked] 这里其实可以试着补救
+                exit_process(&thread.process, -10);
+            }
+        }
+    }
+}
+
+/// 取出线程当前应当处理的最小编号的未被屏蔽信号，同时将其从 pending 集合中移除
+fn pick_pending_signal(process: &Process, thread: &Thread) -> Option<Signal> {
+    let process_pending = process.lock_inner_with(|inner| inner.pending_signals);
+    let signal = thread.lock_inner(|inner| {
+        let mut pending = inner.pending_signals;
+        pending.insert(process_pending);
+        pending.remove(inner.signal_mask);
+        let signal = lowest_signal(pending)?;
+        inner.pending_signals.remove(KSignalSet::from(signal));
+        Some(signal)
+    })?;
+    process.lock_inner_with(|inner| inner.pending_signals.remove(KSignalSet::from(signal)));
+    Some(signal)
+}
+
+/// `KSignalSet` 中从小到大第一个被置位的信号
+fn lowest_signal(set: KSignalSet) -> Option<Signal> {
+    (1..=64u8).find_map(|signum| {
+        let signal = Signal::try_from(signum).ok()?;
+        set.contains(KSignalSet::from(signal)).then_some(signal)
+    })
+}
+
+/// 实现信号的默认行为：终止、忽略、暂停、继续
+fn apply_default_action(thread: &Thread, signal: Signal) {
+    match signal {
+        // 默认行为是忽略的信号
+        Signal::SIGCHLD | Signal::SIGURG | Signal::SIGWINCH => {}
+        // 默认行为是暂停线程，等待 SIGCONT 唤醒
+        Signal::SIGSTOP | Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU => {
+            thread.lock_inner(|inner| inner.thread_status = ThreadStatus::Stopped);
+        }
+        // 默认行为是让已暂停的线程恢复运行
+        Signal::SIGCONT => {
+            thread.lock_inner(|inner| {
+                if inner.thread_status == ThreadStatus::Stopped {
+                    inner.thread_status = ThreadStatus::Ready;
+                }
+            });
+        }
+        // 其余信号的默认行为都是终止整个进程
+        _ => exit_process(&thread.process, 128 + signal as i32),
+    }
+}
+
+/// 向用户栈上压入一个 [`SignalContext`]，并调整 trap context 使得返回用户态后
+/// 落入 signal handler
+///
+/// 错误：
+/// - `EFAULT` 如果当前 `sp` 算出的帧地址不是一段合法的用户内存（例如用户把 `sp`
+///   改成了非法值），这种情况下调用者应当终止进程，而不是把信号帧写到未经检查
+///   的地址上
+fn push_signal_frame(
+    process: &Process,
+    thread: &Thread,
+    signal: Signal,
+    action: &KSignalAction,
+) -> Result {
+    // handler 没有 `SA_RESTORER` 时需要内核的 trampoline，这需要可变借用
+    // `memory_set`，必须在锁线程之前完成
+    let kernel_trampoline = if !action.flags.contains(SignalActionFlags::SA_RESTORER) {
+        Some(process.lock_inner_with(|inner| sig_trampoline_addr(&mut inner.memory_set)))
+    } else {
+        None
+    };
+
+    thread.lock_inner(|inner| {
+        let old_mask = inner.signal_mask;
+        let old_trap_context = inner.trap_context.clone();
+
+        // signal handler 运行在当前用户栈上，在其上预留 `SignalContext` 的空间
+        // 并保持 16 字节对齐
+        let frame_addr =
+            (old_trap_context.sp() - core::mem::size_of::<SignalContext>()) & !0xf;
+        let mut frame_ptr =
+            UserCheckMut::new(frame_addr as *mut SignalContext).check_ptr_mut()?;
+        frame_ptr.clone_from(&SignalContext {
+            old_trap_context,
+            old_mask,
+        });
+
+        inner.trap_context.set_sepc(action.handler as usize);
+        inner.trap_context.set_sp(frame_addr);
+        inner.trap_context.set_a0(signal as u8 as usize);
+        inner
+            .trap_context
+            .set_ra(kernel_trampoline.unwrap_or(action.restorer as usize));
+
+        let mut new_mask = old_mask;
+        new_mask.insert(action.mask);
+        if !action.flags.contains(SignalActionFlags::SA_NODEFER) {
+            new_mask.insert(KSignalSet::from(signal));
+        }
+        inner.signal_mask = new_mask;
+        Ok(0)
+    })
+}
@@ -0,0 +1,72 @@
+//! 信号相关的内核数据结构，与 `syscall::signal` 中暴露的系统调用相对应
+
+mod delivery;
+mod trampoline;
+
+use defines::{
+    signal::{KSignalAction, KSignalSet, Signal},
+    trap_context::TrapContext,
+};
+
+pub use self::delivery::deliver_pending_signal;
+pub use self::trampoline::sig_trampoline_addr;
+
+/// 一个进程安装的全部 `sigaction`，同一进程下的所有线程共享
+///
+/// 下标直接用 [`Signal`] 的判别值，0 号位置空着不用，换来按信号编号直接索引、
+/// 不用再减一的方便
+pub struct SignalHandlers([KSignalAction; 65]);
+
+impl Default for SignalHandlers {
+    fn default() -> Self {
+        // 没有调用过 `sigaction` 的信号，处理方式都是各自的默认行为（`SIG_DFL`）
+        Self(core::array::from_fn(|_| KSignalAction::default()))
+    }
+}
+
+impl SignalHandlers {
+    pub fn action(&self, signal: Signal) -> &KSignalAction {
+        &self.0[signal as usize]
+    }
+
+    pub fn action_mut(&mut self, signal: Signal) -> &mut KSignalAction {
+        &mut self.0[signal as usize]
+    }
+}
+
+/// `sys_rt_sigprocmask` 中 `how` 参数的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SigprocmaskHow {
+    /// 将 `set` 中的信号加入当前掩码
+    SIG_BLOCK = 0,
+    /// 将 `set` 中的信号从当前掩码移除
+    SIG_UNBLOCK = 1,
+    /// 将当前掩码设置为 `set`
+    SIG_SETMASK = 2,
+}
+
+impl TryFrom<usize> for SigprocmaskHow {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::SIG_BLOCK),
+            1 => Ok(Self::SIG_UNBLOCK),
+            2 => Ok(Self::SIG_SETMASK),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 信号处理函数返回时，内核需要恢复的现场。
+///
+/// 会被压入用户栈，由内核提供的 trampoline 或用户的 restorer 负责
+/// 在处理函数返回后将其传回 [`sys_rt_sigreturn`](crate::syscall::signal::sys_rt_sigreturn)
+#[derive(Clone)]
+pub struct SignalContext {
+    /// 进入 signal handler 之前的陷入上下文
+    pub old_trap_context: TrapContext,
+    /// 进入 signal handler 之前的信号掩码
+    pub old_mask: KSignalSet,
+}
@@ -0,0 +1,45 @@
+//! 内核提供的 signal trampoline
+//!
+//! 当用户注册的 `sigaction` 没有带上 `SA_RESTORER` 时，说明用户（一般是没有实现
+//! 该机制的 libc）没有提供处理函数返回后用于调用 `sys_rt_sigreturn` 的代码。
+//! 这种情况下内核需要自己准备一小段代码，在每个进程的地址空间中映射一份，使得
+//! signal handler 返回（`ret`）之后会落入这段代码，从而 `ecall` 进入
+//! `sys_rt_sigreturn`。
+
+use memory::{MapPermission, MemorySet, VirtAddr};
+
+/// `li a7, SYS_RT_SIGRETURN; ecall` 对应的机器码
+///
+/// 之所以手写机器码而不是依赖汇编器在运行时生成，是因为这段代码要被直接拷贝进
+/// 物理页中，不需要走正常的加载流程
+const TRAMPOLINE_CODE: [u8; 8] = {
+    // li a7, 139      (SYS_RT_SIGRETURN)
+    // ecall
+    // 均为 riscv64 下的定长指令，这里直接给出编码后的字节
+    [0x93, 0x08, 0xb0, 0x08, 0x73, 0x00, 0x00, 0x00]
+};
+
+/// signal trampoline 在每个进程地址空间中的虚拟地址
+///
+/// 紧挨着用户代码区域放置，所有进程共用同一个虚拟地址，物理页则按需为每个进程
+/// （更准确地说是每个 [`MemorySet`]）分配一份
+pub const SIG_TRAMPOLINE_VA: usize = defines::config::LOW_ADDRESS_END + defines::config::PAGE_SIZE;
+
+/// 确保 `memory_set` 中已经映射了 signal trampoline 页，返回其虚拟地址
+///
+/// 如果该进程还没有映射过，则惰性地分配一个物理页并写入 trampoline 代码；
+/// 如果已经映射过，直接返回地址即可
+pub fn sig_trampoline_addr(memory_set: &mut MemorySet) -> usize {
+    if memory_set.area_containing(VirtAddr(SIG_TRAMPOLINE_VA)).is_none() {
+        memory_set.insert_framed_area(
+            VirtAddr(SIG_TRAMPOLINE_VA).vpn_floor(),
+            VirtAddr(SIG_TRAMPOLINE_VA + defines::config::PAGE_SIZE).vpn_ceil(),
+            MapPermission::R | MapPermission::X | MapPermission::U,
+        );
+        let frame = memory_set
+            .translate_to_slice_mut(VirtAddr(SIG_TRAMPOLINE_VA), TRAMPOLINE_CODE.len())
+            .expect("trampoline page was just mapped");
+        frame.copy_from_slice(&TRAMPOLINE_CODE);
+    }
+    SIG_TRAMPOLINE_VA
+}
@@ -0,0 +1,46 @@
+use defines::error::{errno, Result};
+
+use crate::{
+    futex::{self, FUTEX_WAIT, FUTEX_WAKE},
+    trap::{enter_syscall, leave_syscall},
+};
+
+/// `FUTEX_PRIVATE_FLAG`/`FUTEX_CLOCK_REALTIME` 等标志位目前都被忽略，只按最低
+/// 几位判断具体操作
+const FUTEX_CMD_MASK: u32 = 0x7f;
+
+/// 参数：
+/// - `uaddr` 用户字的地址，其值会与 `val` 比较
+/// - `futex_op` 操作类型，目前只支持 `FUTEX_WAIT` 和 `FUTEX_WAKE`
+/// - `val` `FUTEX_WAIT` 下是期望值；`FUTEX_WAKE` 下是最多唤醒的线程数
+/// - `timeout` `FUTEX_WAIT` 下可选的超时时间，必须为 NULL（内核的定时器子系统
+///   尚未就绪，暂不支持带超时的等待，见下）
+///
+/// 错误：
+/// - `EINVAL` 如果 `futex_op` 不是目前支持的操作
+/// - `EFAULT` 如果 `uaddr` 指向非法地址
+/// - `EAGAIN` 如果 `FUTEX_WAIT` 时 `*uaddr != val`
+/// - `ENOSYS` 如果 `FUTEX_WAIT` 时 `timeout` 非 NULL
+///
+/// `timeout` 一旦被接受却又被忽略，调用者会以为自己设置了超时、实际上却无限期
+/// 阻塞——比直接报错更危险，所以在定时器子系统就绪之前，这里宁可拒绝也不要悄悄
+/// 丢弃它
+pub async fn sys_futex(
+    uaddr: *const u32,
+    futex_op: u32,
+    val: u32,
+    timeout: *const defines::time::TimeSpec,
+) -> Result {
+    let thread = enter_syscall();
+
+    let result = match futex_op & FUTEX_CMD_MASK {
+        // TODO:[blocked] 等内核的定时器子系统就绪后，支持带超时的 `FUTEX_WAIT`
+        FUTEX_WAIT if !timeout.is_null() => Err(errno::ENOSYS),
+        FUTEX_WAIT => futex::futex_wait(uaddr, val).await,
+        FUTEX_WAKE => futex::futex_wake(uaddr, val),
+        _ => Err(errno::EINVAL),
+    };
+
+    leave_syscall(&thread);
+    result
+}
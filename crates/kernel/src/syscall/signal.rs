@@ -7,7 +7,8 @@ use user_check::{UserCheck, UserCheckMut};
 use crate::{
     hart::local_hart,
     process::exit_process,
-    signal::{SignalContext, SigprocmaskHow},
+    signal::{sig_trampoline_addr, SignalContext, SigprocmaskHow},
+    trap::{enter_syscall, leave_syscall},
 };
 
 /// 设置当前**进程**在收到特定信号时的行为
@@ -25,6 +26,8 @@ pub fn sys_rt_sigaction(
     act: *const KSignalAction,
     old_act: *mut KSignalAction,
 ) -> Result {
+    let thread = enter_syscall();
+
     let Ok(signal) = Signal::try_from(signum as u8) else {
         warn!("use unsupported signal {signum}");
         return Err(errno::EINVAL);
@@ -50,8 +53,14 @@ pub fn sys_rt_sigaction(
         if !act_ptr.flags.contains(SignalActionFlags::SA_RESTORER) {
             // `SA_RESTORER` 表示传入的 restore 字段是有用的
             // 一般而言这个字段由 libc 填写，用于 signal handler 执行结束之后调用 `sys_sigreturn`
-            // 如果没有填写，则 os 需要自己手动做一个 trampoline
-            todo!("[low] sig trampoline does not impl")
+            // 如果没有填写，则内核自己准备一个 trampoline，在信号投递时让 `ra`
+            // 指向它，具体见 `signal::sig_trampoline_addr`
+            debug!("{signal:?}'s action has no SA_RESTORER, kernel trampoline will be used");
+            unsafe {
+                (*local_hart()).curr_process().lock_inner_with(|inner| {
+                    sig_trampoline_addr(&mut inner.memory_set);
+                });
+            }
         }
         unsafe {
             (*local_hart()).curr_process().lock_inner_with(|inner| {
@@ -63,6 +72,7 @@ pub fn sys_rt_sigaction(
         }
     }
 
+    leave_syscall(&thread);
     Ok(0)
 }
 
@@ -83,6 +93,8 @@ pub fn sys_rt_sigprocmask(
     old_set: *mut KSignalSet,
     set_size: usize,
 ) -> Result {
+    let thread = enter_syscall();
+
     if set_size > SIGSET_SIZE_BYTES {
         return Err(errno::EINVAL);
     }
@@ -91,11 +103,9 @@ pub fn sys_rt_sigprocmask(
         trace!("read old_set into {old_set:p}");
         let mut old_set_ptr = UserCheckMut::new(old_set).check_ptr_mut()?;
 
-        unsafe {
-            (*local_hart()).curr_thread().lock_inner_with(|inner| {
-                old_set_ptr.clone_from(&inner.signal_mask);
-            });
-        }
+        thread.lock_inner(|inner| {
+            old_set_ptr.clone_from(&inner.signal_mask);
+        });
     }
 
     let Ok(how) = SigprocmaskHow::try_from(how) else {
@@ -105,23 +115,21 @@ pub fn sys_rt_sigprocmask(
     if !set.is_null() {
         debug!("write signal mask from {set:p} with how = {how:?}");
         let set_ptr = UserCheck::new(set).check_ptr()?;
-        unsafe {
-            (*local_hart())
-                .curr_thread()
-                .lock_inner_with(|inner| match how {
-                    SigprocmaskHow::SIG_BLOCK => inner.signal_mask.insert(*set_ptr),
-                    SigprocmaskHow::SIG_UNBLOCK => inner.signal_mask.remove(*set_ptr),
-                    SigprocmaskHow::SIG_SETMASK => inner.signal_mask = *set_ptr,
-                });
-        }
+        thread.lock_inner(|inner| match how {
+            SigprocmaskHow::SIG_BLOCK => inner.signal_mask.insert(*set_ptr),
+            SigprocmaskHow::SIG_UNBLOCK => inner.signal_mask.remove(*set_ptr),
+            SigprocmaskHow::SIG_SETMASK => inner.signal_mask = *set_ptr,
+        });
     }
 
+    leave_syscall(&thread);
     Ok(0)
 }
 
 pub fn sys_rt_sigreturn() -> Result {
     debug!("sigreturn called");
-    let thread = unsafe { (*local_hart()).curr_thread() };
+    let thread = enter_syscall();
+
     let sp = thread.lock_inner_with(|inner| inner.trap_context.sp());
     let Ok(user_ptr) = UserCheck::new(sp as *mut SignalContext).check_ptr() else {
         // TODO:[blocked] 这里其实可以试着补救
@@ -134,5 +142,6 @@ pub fn sys_rt_sigreturn() -> Result {
         inner.trap_context = user_ptr.old_trap_context.clone();
     });
 
+    leave_syscall(&thread);
     Ok(0)
 }
\ No newline at end of file
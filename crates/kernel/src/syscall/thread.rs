@@ -0,0 +1,26 @@
+use defines::error::Result;
+
+use crate::trap::{enter_syscall, leave_syscall};
+
+/// 设置当前线程的 `clear_child_tid`，用于 `pthread` 在线程退出时清空并 `futex`
+/// 唤醒等待它退出的线程
+///
+/// 返回调用者的 tid
+///
+/// 参数：
+/// - `tidptr` 线程退出时，内核会把 0 写入这个用户地址，并对其执行一次
+///   `FUTEX_WAKE`
+pub fn sys_set_tid_address(tidptr: *mut u32) -> Result {
+    let thread = enter_syscall();
+    let tid = thread.tid;
+    thread.lock_inner(|inner| {
+        inner.clear_child_tid = if tidptr.is_null() {
+            None
+        } else {
+            Some(tidptr as usize)
+        };
+    });
+
+    leave_syscall(&thread);
+    Ok(tid as isize)
+}
@@ -0,0 +1,47 @@
+use defines::{signal::KSignalSet, trap_context::TrapContext};
+
+/// 线程的运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadStatus {
+    /// 可以被调度执行
+    Ready,
+    /// 正在某个 hart 上执行
+    Running,
+    /// 已经退出，等待被回收
+    Zombie,
+    /// 收到 `SIGSTOP`/`SIGTSTP` 等信号后暂停，等待 `SIGCONT`
+    Stopped,
+    /// 阻塞在某个等待队列上，例如 `sys_futex` 的 `FUTEX_WAIT`
+    Blocked,
+}
+
+/// [`super::Thread`] 中需要被锁保护的部分
+pub struct ThreadInner {
+    pub exit_code: i32,
+    pub thread_status: ThreadStatus,
+    pub trap_context: TrapContext,
+    /// 该线程的信号掩码，被屏蔽的信号不会被投递
+    pub signal_mask: KSignalSet,
+    /// 尚未被处理的、只针对该线程的信号（例如 `sys_tkill` 发送的信号）
+    pub pending_signals: KSignalSet,
+    /// 用户态下 `tp` 寄存器的值，即该线程的 TLS 基址
+    ///
+    /// 由 `clone` 的 `tls` 参数或 [`super::spawn_user_thread`] 的调用者设置，
+    /// 在该线程被调度到某个 hart 运行前后，由 trap 相关代码负责和 `tp`/`sscratch`
+    /// 进行交换，见 [`crate::hart`] 中的说明
+    pub user_tp: usize,
+    /// `sys_set_tid_address` 注册的 clear_child_tid 地址
+    ///
+    /// 线程退出时如果该地址非 0，内核需要将其清零并在该地址上执行一次 futex wake
+    pub clear_child_tid: Option<usize>,
+    /// 该线程用户栈的 `(低地址, 高地址)`，由 [`super::UserStackAllocator`] 分配，
+    /// 线程退出时需要凭它归还给分配器
+    pub user_stack: (usize, usize),
+    /// 该线程最近一次被调度到的 hart 编号，由 [`crate::hart::Hart::replace_thread`]
+    /// 维护
+    ///
+    /// 线程阻塞之后可能被另一个 hart 唤醒（例如 `futex_wake`），唤醒者需要凭这个
+    /// 字段用 [`crate::hart::wake_hart`] 给该线程原来所在的 hart 发 IPI，否则那
+    /// 个 hart 的 executor 循环不会知道有新任务就绪
+    pub last_hart: usize,
+}
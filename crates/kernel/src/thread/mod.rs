@@ -1,19 +1,29 @@
 mod inner;
+mod stack;
 mod user;
 
-use alloc::sync::Weak;
-use defines::config::{LOW_ADDRESS_END, PAGE_SIZE, USER_STACK_SIZE};
+use alloc::sync::{Arc, Weak};
+use defines::config::PAGE_SIZE;
+use defines::signal::KSignalSet;
 use defines::trap_context::TrapContext;
 use memory::{MapPermission, MemorySet, VirtAddr};
 use spin::Mutex;
+use user_check::UserCheckMut;
 
-use crate::process::Process;
+use crate::{futex, process::Process};
 
 use self::inner::ThreadInner;
 
 pub use self::inner::ThreadStatus;
+pub use self::stack::UserStackAllocator;
 pub use self::user::spawn_user_thread;
 
+/// 用户栈默认大小，也是 `clone`/`pthread_attr_setstacksize` 没有显式指定栈大小
+/// 时使用的大小
+pub const DEFAULT_USER_STACK_SIZE: usize = defines::config::USER_STACK_SIZE;
+/// 允许请求的最小用户栈大小，小于它的请求会被 clamp 上去
+pub const MIN_USER_STACK_SIZE: usize = PAGE_SIZE * 4;
+
 /// 进程控制块
 pub struct Thread {
     pub tid: usize,
@@ -23,7 +33,12 @@ pub struct Thread {
 }
 
 impl Thread {
-    pub fn new(process: Weak<Process>, tid: usize, trap_context: TrapContext) -> Self {
+    pub fn new(
+        process: Weak<Process>,
+        tid: usize,
+        trap_context: TrapContext,
+        user_stack: (usize, usize),
+    ) -> Self {
         Self {
             tid,
             process,
@@ -31,6 +46,12 @@ impl Thread {
                 exit_code: 0,
                 thread_status: ThreadStatus::Ready,
                 trap_context,
+                signal_mask: KSignalSet::empty(),
+                pending_signals: KSignalSet::empty(),
+                user_tp: 0,
+                clear_child_tid: None,
+                user_stack,
+                last_hart: 0,
             }),
         }
     }
@@ -40,45 +61,73 @@ impl Thread {
         f(&mut self.inner.lock())
     }
 
-    /// 分配用户栈，一般用于创建新线程。返回用户栈高地址
+    /// 分配用户栈，一般用于创建新线程。返回 `(低地址, 高地址)`
     ///
-    /// 注意 `memory_set` 是进程的 `MemorySet`
-    pub fn alloc_user_stack(tid: usize, memory_set: &mut MemorySet) -> usize {
-        // 分配用户栈
-        let ustack_low_addr = Self::user_stack_low_addr(tid);
-        log::debug!("stack low addr: {:#x}", ustack_low_addr);
-        let ustack_high_addr = ustack_low_addr + USER_STACK_SIZE;
-        log::debug!("stack high addr: {:#x}", ustack_high_addr);
+    /// `requested_size` 是调用者（`clone` 的 `stack` 参数、
+    /// `pthread_attr_setstacksize` 等）请求的栈大小，会被 clamp 到
+    /// [`MIN_USER_STACK_SIZE`] 以上；`stack_allocator` 和 `memory_set` 都属于
+    /// 进程而不是某个固定的 `tid`，因此线程退出后空出来的栈区域可以被其他线程
+    /// 复用，不会浪费地址空间
+    pub fn alloc_user_stack(
+        stack_allocator: &mut UserStackAllocator,
+        memory_set: &mut MemorySet,
+        requested_size: usize,
+    ) -> (usize, usize) {
+        let stack_size = requested_size.max(MIN_USER_STACK_SIZE);
+        let (low, high) = stack_allocator.alloc(stack_size);
+        log::debug!("stack low addr: {:#x}, high addr: {:#x}", low, high);
         memory_set.insert_framed_area(
-            VirtAddr(ustack_low_addr).vpn_floor(),
-            VirtAddr(ustack_high_addr).vpn_ceil(),
+            VirtAddr(low).vpn_floor(),
+            VirtAddr(high).vpn_ceil(),
             MapPermission::R | MapPermission::W | MapPermission::U,
         );
-        ustack_high_addr
-    }
-
-    /// 获取当前线程用户栈的低地址，即高地址减去用户栈大小
-    fn user_stack_low_addr(tid: usize) -> usize {
-        Self::user_stack_high_addr(tid) - USER_STACK_SIZE
+        (low, high)
     }
 
-    /// 获取当前线程用户栈的高地址
-    fn user_stack_high_addr(tid: usize) -> usize {
-        // 注意每个用户栈后都会有一个 Guard Page
-        LOW_ADDRESS_END - tid * (USER_STACK_SIZE + PAGE_SIZE)
-    }
-
-    /// 释放用户栈。一般是单个线程退出时使用。
+    /// 释放用户栈。一般是单个线程退出时使用
     ///
-    /// 注意 `memory_set` 是进程的 `MemorySet`
-    fn dealloc_user_stack(&self, memory_set: &mut MemorySet) {
-        // 手动取消用户栈的映射
-        let user_stack_low_addr = VirtAddr(Self::user_stack_low_addr(self.tid));
-        memory_set.remove_area_with_start_vpn(user_stack_low_addr.vpn());
+    /// 注意 `memory_set`、`stack_allocator` 都是进程级别的
+    pub fn dealloc_user_stack(
+        &self,
+        stack_allocator: &mut UserStackAllocator,
+        memory_set: &mut MemorySet,
+    ) {
+        let (low, high) = self.lock_inner(|inner| inner.user_stack);
+        // 手动取消用户栈的映射，再把这段区域还给分配器供以后复用
+        memory_set.remove_area_with_start_vpn(VirtAddr(low).vpn());
+        stack_allocator.dealloc(low, high);
     }
 
     pub async fn yield_now(&self) {
         self.inner.lock().thread_status = ThreadStatus::Ready;
         executor::yield_now().await
     }
+}
+
+/// 终止单个线程：标记为 zombie、处理 `clear_child_tid`、释放用户栈，并把它从
+/// 进程的线程列表中移除
+///
+/// 这里只处理线程自身的收尾；如果 `thread` 是 `process` 最后一个线程，调用者
+/// 还需要自行调用 [`crate::process::exit_process`] 来处理进程级别的退出
+pub fn exit_thread(process: &Process, thread: &Arc<Thread>, exit_code: i32) {
+    let clear_child_tid = thread.lock_inner(|inner| {
+        inner.exit_code = exit_code;
+        inner.thread_status = ThreadStatus::Zombie;
+        inner.clear_child_tid
+    });
+
+    // `set_tid_address` 约定线程退出时把这个地址清零并 `FUTEX_WAKE` 它，用于
+    // 实现 `pthread_join`；用户指针可能已经失效（例如所在的内存被提前释放），
+    // 此时线程反正都要退出了，跳过这次唤醒即可，不应该阻止退出流程继续
+    if let Some(tidptr) = clear_child_tid {
+        if let Ok(mut tid_ptr) = UserCheckMut::new(tidptr as *mut u32).check_ptr_mut() {
+            tid_ptr.clone_from(&0u32);
+            let _ = futex::futex_wake(tidptr as *const u32, 1);
+        }
+    }
+
+    process.lock_inner_with(|inner| {
+        thread.dealloc_user_stack(&mut inner.stack_allocator, &mut inner.memory_set);
+        inner.threads.retain(|t| !Arc::ptr_eq(t, thread));
+    });
 }
\ No newline at end of file
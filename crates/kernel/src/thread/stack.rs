@@ -0,0 +1,61 @@
+//! 进程内用户栈地址区域的分配器
+
+use alloc::vec::Vec;
+
+use defines::config::{LOW_ADDRESS_END, PAGE_SIZE};
+
+/// 为同一个进程的所有线程分配用户栈区域
+///
+/// 不再像之前那样按 `tid` 线性推算栈的位置——那样做既无法支持调用者请求的栈
+/// 大小，又会导致 `tid` 增长之后必然耗尽低地址空间：一个线程退出之后，它的栈槽
+/// 位永远无法被别的线程复用。现在改成一个从高地址向低地址增长的分配器：优先复
+/// 用 `free` 中记录的、已经被释放的区域，找不到合适的再从未使用过的区域切一块
+/// 下来。每个栈后面都留有一个 guard page，不计入返回的 `(low, high)` 范围
+pub struct UserStackAllocator {
+    /// 还未被任何线程使用过的区域的高地址上沿，向下递减
+    frontier: usize,
+    /// 曾经分配过、现在已经释放的区域，记为 `(low, high)`，`high` 不含 guard page
+    free: Vec<(usize, usize)>,
+}
+
+impl UserStackAllocator {
+    pub const fn new() -> Self {
+        Self {
+            frontier: LOW_ADDRESS_END,
+            free: Vec::new(),
+        }
+    }
+
+    /// 分配一块至少 `size` 字节的栈区域，返回 `(low, high)`
+    ///
+    /// `size` 会先向上取整到页大小，使 guard page 不会被相邻栈的映射占用
+    pub fn alloc(&mut self, size: usize) -> (usize, usize) {
+        let size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        if let Some(i) = self
+            .free
+            .iter()
+            .position(|&(low, high)| high - low >= size)
+        {
+            let (low, high) = self.free.swap_remove(i);
+            // 从区域顶部切出需要的大小。切出来的栈和剩下的部分之间也要留一个
+            // guard page，否则剩下的部分以后再分配出去时会跟这次的栈紧贴在一起
+            let used_low = high - size;
+            if used_low > low + PAGE_SIZE {
+                self.free.push((low, used_low - PAGE_SIZE));
+            }
+            return (used_low, high);
+        }
+
+        // 没有能复用的区域，从未分配过的区域里切一块，中间留一个 guard page
+        let high = self.frontier;
+        let low = high - size;
+        self.frontier = low - PAGE_SIZE;
+        (low, high)
+    }
+
+    /// 归还一块曾经由 [`Self::alloc`] 分配出去的 `(low, high)` 区域，供之后的
+    /// 分配复用
+    pub fn dealloc(&mut self, low: usize, high: usize) {
+        self.free.push((low, high));
+    }
+}
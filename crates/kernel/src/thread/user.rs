@@ -0,0 +1,38 @@
+//! 创建新的用户线程
+
+use alloc::sync::Arc;
+use defines::trap_context::TrapContext;
+
+use crate::process::Process;
+
+use super::Thread;
+
+/// 创建一个新的用户线程并加入 `process`
+///
+/// 参数：
+/// - `tls` 对应 `clone` 系统调用中 `CLONE_SETTLS` 传入的 TLS 基址，也就是该线程
+///   用户态下 `tp` 寄存器的初始值；没有指定时传 0 即可
+/// - `stack_size` 对应 `clone` 的 `stack` 参数或 `pthread_attr_setstacksize`
+///   请求的栈大小；没有指定时传 [`super::DEFAULT_USER_STACK_SIZE`]
+pub fn spawn_user_thread(
+    process: &Arc<Process>,
+    tid: usize,
+    entry: usize,
+    stack_size: usize,
+    tls: usize,
+) -> Arc<Thread> {
+    let (user_stack_low, user_stack_high) = process.lock_inner_with(|inner| {
+        Thread::alloc_user_stack(&mut inner.stack_allocator, &mut inner.memory_set, stack_size)
+    });
+
+    let trap_context = TrapContext::app_init_context(entry, user_stack_high);
+    let thread = Arc::new(Thread::new(
+        Arc::downgrade(process),
+        tid,
+        trap_context,
+        (user_stack_low, user_stack_high),
+    ));
+    thread.lock_inner(|inner| inner.user_tp = tls);
+    process.lock_inner_with(|inner| inner.threads.push(Arc::clone(&thread)));
+    thread
+}
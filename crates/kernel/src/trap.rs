@@ -0,0 +1,35 @@
+//! 陷入内核前后的通用收尾工作
+//!
+//! 目前仓库里还没有一个真正的系统调用分发表（各个调用点分散在
+//! `syscall::*::sys_*` 函数里），没法把这里的两个函数挂在唯一的陷入入口/出口
+//! 上；作为折中，把原本要在每个系统调用里重复的 `on_trap_enter`/
+//! `on_trap_leave`（`tp`/`sscratch` 收尾）和 `deliver_pending_signal`（信号
+//! 投递）收拢成这一对函数，每个系统调用处理函数只需要在开头/结尾各调用一次。
+//! 一旦将来接上真正的分发表，也只需要把这两个调用搬到分发表里，各个 `sys_*`
+//! 自身不用再改
+
+use alloc::sync::Arc;
+
+use crate::{
+    hart::{local_hart, on_trap_enter, on_trap_leave},
+    signal::deliver_pending_signal,
+    thread::Thread,
+};
+
+/// 进入系统调用处理之前调用：取出当前线程并完成 `tp`/`sscratch` 的收尾
+///
+/// 返回当前线程，调用者应当在处理结束时把它传给 [`leave_syscall`]
+pub fn enter_syscall() -> Arc<Thread> {
+    let thread = unsafe { (*local_hart()).curr_thread() };
+    on_trap_enter(&thread);
+    thread
+}
+
+/// 系统调用处理完毕、即将返回用户态之前调用：投递 pending 的信号，并完成
+/// `tp`/`sscratch` 的收尾
+pub fn leave_syscall(thread: &Thread) {
+    if let Some(process) = thread.process.upgrade() {
+        deliver_pending_signal(&process, thread);
+    }
+    on_trap_leave(thread);
+}